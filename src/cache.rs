@@ -0,0 +1,85 @@
+use std::{
+    fs,
+    hash::{Hash, Hasher},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// An on-disk snapshot of a previous fetch, written by `--cache <FILE>` and
+/// reused by later runs while it's within `--cache-ttl`.
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    /// Identifies the exact request (names, paths, decryption flag) this
+    /// cache entry was fetched for, so a changed request can't read a stale
+    /// entry for a different request.
+    fingerprint: u64,
+    fetched_at_secs: u64,
+    params: Vec<(String, String)>,
+}
+
+fn fingerprint(names: &[String], paths: &[String], no_decrypt: bool, extra: &str) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    names.hash(&mut hasher);
+    paths.hash(&mut hasher);
+    no_decrypt.hash(&mut hasher);
+    extra.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn now_secs() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Load cached params for this exact request if `path` holds a fresh,
+/// matching entry. Returns `None` on a cache miss, expiry, or mismatch —
+/// never an error, since a cache problem should just fall back to fetching.
+pub(crate) fn load(
+    path: &Path,
+    ttl: Duration,
+    names: &[String],
+    paths: &[String],
+    no_decrypt: bool,
+    extra: &str,
+) -> Result<Option<Vec<(String, String)>>> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Ok(None);
+    };
+    let Ok(cache) = serde_json::from_str::<CacheFile>(&text) else {
+        return Ok(None);
+    };
+
+    if cache.fingerprint != fingerprint(names, paths, no_decrypt, extra) {
+        return Ok(None);
+    }
+
+    let Ok(now) = now_secs() else {
+        return Ok(None);
+    };
+    let age = Duration::from_secs(now.saturating_sub(cache.fetched_at_secs));
+    if age > ttl {
+        return Ok(None);
+    }
+
+    Ok(Some(cache.params))
+}
+
+/// Write `params` to `path` as the cache entry for this exact request.
+pub(crate) fn save(
+    path: &Path,
+    names: &[String],
+    paths: &[String],
+    no_decrypt: bool,
+    extra: &str,
+    params: &[(String, String)],
+) -> Result<()> {
+    let cache = CacheFile {
+        fingerprint: fingerprint(names, paths, no_decrypt, extra),
+        fetched_at_secs: now_secs()?,
+        params: params.to_vec(),
+    };
+    let text = serde_json::to_string(&cache).wrap_err("serializing cache entry")?;
+    fs::write(path, text).wrap_err_with(|| format!("writing cache file {}", path.display()))
+}
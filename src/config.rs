@@ -0,0 +1,164 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use eyre::{Context, Result};
+use serde::Deserialize;
+
+use crate::{Args, Export, ExportPath};
+
+/// The fully resolved settings `ssm-env` runs with, after merging every
+/// [`ConfigurationSource`].
+#[derive(Debug, Default)]
+pub(crate) struct Configuration {
+    pub(crate) no_decrypt: bool,
+    pub(crate) ignore: bool,
+    pub(crate) export: Vec<Export>,
+    pub(crate) export_path: Vec<ExportPath>,
+}
+
+/// Something that can contribute export/decryption settings to a
+/// [`Configuration`]. `Args` (the CLI) and `FileConfig` (a TOML file) both
+/// implement this so they can be merged the same way.
+pub(crate) trait ConfigurationSource {
+    fn export(&self) -> Vec<Export>;
+    fn export_path(&self) -> Vec<ExportPath>;
+    /// `None` means "not specified by this source", so a source further down
+    /// the precedence chain (or the hardcoded default of `false`) can apply
+    /// instead.
+    fn no_decrypt(&self) -> Option<bool>;
+    fn ignore(&self) -> Option<bool>;
+}
+
+impl ConfigurationSource for Args {
+    fn export(&self) -> Vec<Export> {
+        self.export.clone()
+    }
+
+    fn export_path(&self) -> Vec<ExportPath> {
+        self.export_path.clone()
+    }
+
+    fn no_decrypt(&self) -> Option<bool> {
+        self.no_decrypt
+    }
+
+    fn ignore(&self) -> Option<bool> {
+        self.ignore
+    }
+}
+
+impl Configuration {
+    /// Merge `file` (the `--config` TOML, if any) with `cli`, with CLI flags
+    /// and `--export`/`--export-path` entries taking precedence over the
+    /// file when both name the same thing.
+    pub(crate) fn merge(cli: &impl ConfigurationSource, file: &impl ConfigurationSource) -> Self {
+        let mut export: HashMap<String, Export> = file
+            .export()
+            .into_iter()
+            .map(|e| (e.env.clone(), e))
+            .collect();
+        for e in cli.export() {
+            export.insert(e.env.clone(), e);
+        }
+        let mut export = export.into_values().collect::<Vec<_>>();
+        export.sort_by(|a, b| a.env.cmp(&b.env));
+
+        // Preserve file-then-CLI ordering (not just key presence) so that when two
+        // different paths normalize to the same env var name, the CLI's entry is
+        // still the one applied last and therefore wins, matching "CLI takes
+        // precedence over the file".
+        let cli_paths: HashSet<String> = cli
+            .export_path()
+            .into_iter()
+            .map(|p| p.path)
+            .collect();
+        let mut export_path: Vec<ExportPath> = file
+            .export_path()
+            .into_iter()
+            .filter(|p| !cli_paths.contains(&p.path))
+            .collect();
+        let mut seen_paths: HashSet<String> = HashSet::new();
+        for p in cli.export_path() {
+            if seen_paths.insert(p.path.clone()) {
+                export_path.push(p);
+            }
+        }
+
+        Self {
+            no_decrypt: cli.no_decrypt().or(file.no_decrypt()).unwrap_or(false),
+            ignore: cli.ignore().or(file.ignore()).unwrap_or(false),
+            export,
+            export_path,
+        }
+    }
+}
+
+/// The shape of a `--config` TOML file, e.g.:
+///
+/// ```toml
+/// no_decrypt = false
+/// ignore = false
+/// paths = ["/my-app"]
+///
+/// [[export]]
+/// env = "DATABASE_URL"
+/// param = "/my-app/db-url"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct FileConfig {
+    #[serde(default, rename = "export")]
+    exports: Vec<FileExport>,
+    #[serde(default)]
+    paths: Vec<String>,
+    #[serde(default)]
+    no_decrypt: Option<bool>,
+    #[serde(default)]
+    ignore: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileExport {
+    env: String,
+    param: Option<String>,
+}
+
+impl FileConfig {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .wrap_err_with(|| format!("reading config file {}", path.display()))?;
+        toml::from_str(&text).wrap_err_with(|| format!("parsing config file {}", path.display()))
+    }
+}
+
+impl ConfigurationSource for FileConfig {
+    fn export(&self) -> Vec<Export> {
+        self.exports
+            .iter()
+            .map(|e| Export {
+                env: e.env.clone(),
+                param: e.param.clone(),
+            })
+            .collect()
+    }
+
+    fn export_path(&self) -> Vec<ExportPath> {
+        self.paths
+            .iter()
+            .map(|path| ExportPath {
+                path: path.clone(),
+                normalize: None,
+            })
+            .collect()
+    }
+
+    fn no_decrypt(&self) -> Option<bool> {
+        self.no_decrypt
+    }
+
+    fn ignore(&self) -> Option<bool> {
+        self.ignore
+    }
+}
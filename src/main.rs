@@ -6,23 +6,82 @@
     clippy::expect_used
 )]
 
-use std::{collections::HashMap, process::ExitCode, str::FromStr};
+mod cache;
+mod config;
+mod source;
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+    time::Duration,
+};
 
-use aws_sdk_ssm::{types::Parameter, Client};
 use clap::{command, Parser};
-use eyre::Result;
+use config::{Configuration, FileConfig};
+use eyre::{eyre, Context, Result};
+use handlebars::Handlebars;
+use log::warn;
+use source::{AnySource, ParameterSource, SecretsManagerSource, Source, SsmSource};
 use tokio::process::Command;
 
+/// `GetParameters` accepts at most this many names per request.
+const MAX_PARAMETERS_PER_BATCH: usize = 10;
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Decrypt SecureStrings
+    /// Load export/export-path/no-decrypt/ignore settings from a TOML file.
+    /// CLI flags take precedence over values in the file.
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Do not decrypt SecureStrings. Passing this explicitly overrides a `--config`
+    /// file's `no_decrypt` setting in either direction (e.g. `--no-decrypt=false`
+    /// forces decryption back on even if the file sets it `true`).
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    no_decrypt: Option<bool>,
+
+    /// Ignore (clear) existing environment variables. Passing this
+    /// explicitly overrides a `--config` file's `ignore` setting in either
+    /// direction.
+    #[arg(long, short, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    ignore: Option<bool>,
+
+    /// Warn instead of failing when an exported parameter does not exist.
+    #[arg(long)]
+    allow_missing: bool,
+
+    /// Which AWS secret store to fetch `--export`/`--export-path` values from.
+    #[arg(long, value_enum, default_value = "ssm")]
+    source: Source,
+
+    /// With `--source secretsmanager`, split a secret whose value is a JSON
+    /// object into one env var per field instead of passing it through whole.
     #[arg(long)]
-    no_decrypt: bool,
+    secrets_json: bool,
+
+    /// Cache fetched parameters to this file and reuse them on later runs
+    /// within `--cache-ttl`, instead of hitting SSM/Secrets Manager again.
+    /// The cache is invalidated automatically if the requested names/paths
+    /// or `--no-decrypt` change.
+    #[arg(long, value_name = "FILE")]
+    cache: Option<PathBuf>,
+
+    /// How long a `--cache` entry stays valid for, in seconds.
+    #[arg(long, value_name = "SECONDS", default_value_t = 300, requires = "cache")]
+    cache_ttl: u64,
+
+    /// Render a Handlebars template (with `{{parameter_name}}` placeholders)
+    /// using the fetched parameters before running the utility. The parameters
+    /// are still exported into the utility's environment as well. Requires `--render`.
+    #[arg(long, value_name = "IN", requires = "render")]
+    template: Option<PathBuf>,
 
-    /// Ignore (clear) existing environment variables.
-    #[arg(long, short)]
-    ignore: bool,
+    /// Where to write the rendered `--template` output. Requires `--template`.
+    #[arg(long, value_name = "OUT", requires = "template")]
+    render: Option<PathBuf>,
 
     /// Export an aws ssm parameter to an environment variable. The parameter name can
     /// be specified if it differs from the environment variable.
@@ -30,9 +89,19 @@ struct Args {
     export: Vec<Export>,
 
     /// Export one level of a path of aws ssm parameters to environment variables. All
-    /// parameters under the prefix will be exported as environment variables.
-    #[arg(long, short = 'P', value_name = "PATH")]
-    export_path: Vec<String>,
+    /// parameters under the prefix will be exported as environment variables. Append
+    /// `=true`/`=false` to override `--normalize` for just this path.
+    #[arg(long, short = 'P', value_name = "PATH[=true|false]")]
+    export_path: Vec<ExportPath>,
+
+    /// Normalize generated environment variable keys: uppercase them and replace `/`
+    /// and `-` with `_`, trimming leading/trailing separators.
+    #[arg(long)]
+    normalize: bool,
+
+    /// Prepend this string to every key generated by `--export-path`.
+    #[arg(long, value_name = "STR")]
+    prefix: Option<String>,
 
     /// The command to run after setting the environment variables from the ssm parameters.
     utility: String,
@@ -42,12 +111,28 @@ struct Args {
 }
 
 #[derive(Clone, Debug)]
-struct Export {
-    env: String,
-    param: Option<String>,
+pub(crate) struct Export {
+    pub(crate) env: String,
+    pub(crate) param: Option<String>,
 }
 
-impl Args {
+/// An `--export-path` argument, with an optional per-path override of the
+/// global `--normalize` setting.
+#[derive(Clone, Debug)]
+pub(crate) struct ExportPath {
+    pub(crate) path: String,
+    pub(crate) normalize: Option<bool>,
+}
+
+impl ExportPath {
+    /// A string that uniquely identifies this path and its override, for
+    /// use as part of a `--cache` fingerprint.
+    fn cache_key(&self) -> String {
+        format!("{}={:?}", self.path, self.normalize)
+    }
+}
+
+impl Configuration {
     fn parameter_names(&self) -> Vec<String> {
         self.export
             .iter()
@@ -74,45 +159,100 @@ async fn main() -> Result<ExitCode> {
     env_logger::init();
 
     let args = Args::parse();
-    let config = aws_config::load_from_env().await;
-    let client = Client::new(&config);
-    let names = args.parameter_names();
-    let mut params: Vec<(String, String)> = Vec::new();
-    if !names.is_empty() {
-        let exports = args.export_names();
-        let p = client
-            .get_parameters()
-            .set_names(Some(names))
-            .set_with_decryption(Some(!args.no_decrypt))
-            .send()
-            .await?
-            .parameters
-            .into_iter()
-            .flatten()
-            .filter_map(|p| filter_export(p, &exports))
-            .collect::<Vec<_>>();
-
-        params.extend(p);
-    }
-    let paths = args.export_path;
-    for path in paths {
-        let p = client
-            .get_parameters_by_path()
-            .set_path(Some(path.clone()))
-            .set_with_decryption(Some(!args.no_decrypt))
-            .send()
-            .await?
-            .parameters
-            .into_iter()
-            .flatten()
-            .filter_map(|param| filter_export_path(param, &path))
-            .collect::<Vec<_>>();
-
-        params.extend(p);
+    let file_config = match &args.config {
+        Some(path) => FileConfig::load(path)?,
+        None => FileConfig::default(),
+    };
+    let settings = Configuration::merge(&args, &file_config);
+
+    let names = settings.parameter_names();
+    let exports = settings.export_names();
+    let cache_paths: Vec<String> = settings.export_path.iter().map(ExportPath::cache_key).collect();
+    let mut cache_aliases: Vec<(&String, &String)> = exports.iter().collect();
+    cache_aliases.sort();
+    let source_kind = args.source;
+    let normalize = args.normalize;
+    let prefix = &args.prefix;
+    let secrets_json = args.secrets_json;
+    let cache_extra = format!(
+        "source={source_kind:?}/normalize={normalize}/prefix={prefix:?}/secrets_json={secrets_json}/aliases={cache_aliases:?}"
+    );
+    let cached = match &args.cache {
+        Some(cache_path) => cache::load(
+            cache_path,
+            Duration::from_secs(args.cache_ttl),
+            &names,
+            &cache_paths,
+            settings.no_decrypt,
+            &cache_extra,
+        )?,
+        None => None,
+    };
+
+    let params = match cached {
+        Some(params) => params,
+        None => {
+            let aws_config = aws_config::load_from_env().await;
+            let source = match args.source {
+                Source::Ssm => AnySource::Ssm(SsmSource::new(aws_sdk_ssm::Client::new(&aws_config))),
+                Source::SecretsManager => AnySource::SecretsManager(SecretsManagerSource::new(
+                    aws_sdk_secretsmanager::Client::new(&aws_config),
+                    args.secrets_json,
+                )),
+            };
+
+            let mut params: Vec<(String, String)> = Vec::new();
+            if !names.is_empty() {
+                let fetch = source
+                    .fetch_names(&names, !settings.no_decrypt, &exports)
+                    .await?;
+
+                params.extend(
+                    fetch
+                        .params
+                        .into_iter()
+                        .map(|(name, value)| rename_export(name, value, &exports)),
+                );
+
+                if !fetch.invalid.is_empty() {
+                    let joined = fetch.invalid.join(", ");
+                    if args.allow_missing {
+                        warn!("parameters not found: {joined}");
+                    } else {
+                        return Err(eyre!("parameters not found: {joined}"));
+                    }
+                }
+            }
+            for export_path in &settings.export_path {
+                let fetched = source
+                    .fetch_path(&export_path.path, !settings.no_decrypt)
+                    .await?;
+                params.extend(fetched.into_iter().map(|(name, value)| {
+                    strip_path_prefix(name, value, export_path, args.normalize, args.prefix.as_deref())
+                }));
+            }
+
+            if let Some(cache_path) = &args.cache {
+                cache::save(
+                    cache_path,
+                    &names,
+                    &cache_paths,
+                    settings.no_decrypt,
+                    &cache_extra,
+                    &params,
+                )?;
+            }
+
+            params
+        }
+    };
+
+    if let (Some(template), Some(render)) = (&args.template, &args.render) {
+        render_template(&params, template, render)?;
     }
 
     let mut cmd = Command::new(args.utility);
-    if args.ignore {
+    if settings.ignore {
         cmd.env_clear();
     }
     cmd.args(args.arguments);
@@ -122,38 +262,64 @@ async fn main() -> Result<ExitCode> {
     Ok(ExitCode::from(u8::try_from(code).unwrap_or(1)))
 }
 
-fn filter_export(param: Parameter, exports: &HashMap<String, String>) -> Option<(String, String)> {
-    if let Parameter {
-        name: Some(name),
-        value: Some(value),
-        ..
-    } = param
-    {
-        let name = exports.get(&name).unwrap_or(&name);
-        Some((name.clone(), value))
-    } else {
-        None
-    }
+fn rename_export(name: String, value: String, exports: &HashMap<String, String>) -> (String, String) {
+    let name = exports.get(&name).cloned().unwrap_or(name);
+    (name, value)
 }
 
-fn filter_export_path(param: Parameter, path: &str) -> Option<(String, String)> {
-    if let Parameter {
-        name: Some(name),
-        value: Some(value),
-        ..
-    } = param
-    {
-        let prefix = if path.ends_with('/') {
-            path.to_owned()
-        } else {
-            format!("{path}/")
-        };
-        let name = name.strip_prefix(&prefix).unwrap_or(&name);
-        Some((name.to_owned(), value))
+fn strip_path_prefix(
+    name: String,
+    value: String,
+    export_path: &ExportPath,
+    normalize: bool,
+    prefix: Option<&str>,
+) -> (String, String) {
+    let path = &export_path.path;
+    let strip = if path.ends_with('/') {
+        path.to_owned()
     } else {
-        None
+        format!("{path}/")
+    };
+    let mut name = name.strip_prefix(&strip).unwrap_or(&name).to_owned();
+
+    if export_path.normalize.unwrap_or(normalize) {
+        name = normalize_key(&name);
+    }
+    if let Some(prefix) = prefix {
+        name = format!("{prefix}{name}");
     }
+
+    (name, value)
+}
+
+/// Turn a stripped parameter name like `db-host` into a conventional
+/// environment variable key like `DB_HOST`.
+fn normalize_key(name: &str) -> String {
+    name.trim_matches(['/', '-'])
+        .to_uppercase()
+        .replace(['/', '-'], "_")
+}
+
+fn render_template(params: &[(String, String)], template: &Path, output: &Path) -> Result<()> {
+    let source = std::fs::read_to_string(template)
+        .wrap_err_with(|| format!("reading template {}", template.display()))?;
+    let context: HashMap<&str, &str> = params
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+
+    let mut handlebars = Handlebars::new();
+    // Config files, not HTML, are the output here, so don't HTML-escape values
+    // like `&`/`"` in e.g. a DB URL or JSON blob.
+    handlebars.register_escape_fn(handlebars::no_escape);
+    let rendered = handlebars
+        .render_template(&source, &context)
+        .wrap_err_with(|| format!("rendering template {}", template.display()))?;
+
+    std::fs::write(output, rendered)
+        .wrap_err_with(|| format!("writing rendered template to {}", output.display()))
 }
+
 impl FromStr for Export {
     type Err = &'static str;
 
@@ -170,3 +336,25 @@ impl FromStr for Export {
         }
     }
 }
+
+impl FromStr for ExportPath {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((path, "true")) => Ok(Self {
+                path: path.to_owned(),
+                normalize: Some(true),
+            }),
+            Some((path, "false")) => Ok(Self {
+                path: path.to_owned(),
+                normalize: Some(false),
+            }),
+            Some(_) => Err("expected PATH or PATH=true|false"),
+            None => Ok(Self {
+                path: s.to_owned(),
+                normalize: None,
+            }),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use aws_sdk_secretsmanager::{
+    operation::get_secret_value::GetSecretValueError, Client as SecretsManagerClient,
+};
+use aws_sdk_ssm::Client as SsmClient;
+use clap::ValueEnum;
+use eyre::Result;
+
+use crate::MAX_PARAMETERS_PER_BATCH;
+
+/// Which AWS secret store to fetch parameters from.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub(crate) enum Source {
+    #[default]
+    Ssm,
+    #[value(name = "secretsmanager")]
+    SecretsManager,
+}
+
+/// The result of resolving a set of `--export` names: the (name, value)
+/// pairs found, plus any names that did not resolve to anything.
+pub(crate) struct NamedFetch {
+    pub(crate) params: Vec<(String, String)>,
+    pub(crate) invalid: Vec<String>,
+}
+
+/// A backend that can resolve `--export` names and `--export-path` prefixes
+/// into environment variable values. `SsmSource` and `SecretsManagerSource`
+/// both implement this so `main` doesn't need to know which store it's
+/// talking to.
+///
+/// `AnySource` dispatches between implementors with a plain `match`, never
+/// as a trait object, so the usual `dyn`-compatibility concern behind the
+/// `async_fn_in_trait` lint doesn't apply here.
+#[allow(async_fn_in_trait)]
+pub(crate) trait ParameterSource {
+    async fn fetch_names(
+        &self,
+        names: &[String],
+        with_decryption: bool,
+        exports: &HashMap<String, String>,
+    ) -> Result<NamedFetch>;
+    async fn fetch_path(&self, path: &str, with_decryption: bool) -> Result<Vec<(String, String)>>;
+}
+
+pub(crate) struct SsmSource {
+    client: SsmClient,
+}
+
+impl SsmSource {
+    pub(crate) fn new(client: SsmClient) -> Self {
+        Self { client }
+    }
+}
+
+impl ParameterSource for SsmSource {
+    async fn fetch_names(
+        &self,
+        names: &[String],
+        with_decryption: bool,
+        _exports: &HashMap<String, String>,
+    ) -> Result<NamedFetch> {
+        let mut params = Vec::new();
+        let mut invalid = Vec::new();
+        for batch in names.chunks(MAX_PARAMETERS_PER_BATCH) {
+            let output = self
+                .client
+                .get_parameters()
+                .set_names(Some(batch.to_vec()))
+                .set_with_decryption(Some(with_decryption))
+                .send()
+                .await?;
+
+            invalid.extend(output.invalid_parameters.into_iter().flatten());
+            params.extend(
+                output
+                    .parameters
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|p| Some((p.name?, p.value?))),
+            );
+        }
+        Ok(NamedFetch { params, invalid })
+    }
+
+    async fn fetch_path(&self, path: &str, with_decryption: bool) -> Result<Vec<(String, String)>> {
+        let mut params = Vec::new();
+        let mut next_token = None;
+        loop {
+            let output = self
+                .client
+                .get_parameters_by_path()
+                .set_path(Some(path.to_owned()))
+                .set_with_decryption(Some(with_decryption))
+                .set_next_token(next_token)
+                .send()
+                .await?;
+
+            params.extend(
+                output
+                    .parameters
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|p| Some((p.name?, p.value?))),
+            );
+
+            next_token = output.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+        Ok(params)
+    }
+}
+
+/// Dispatches to whichever backend `--source` selected.
+pub(crate) enum AnySource {
+    Ssm(SsmSource),
+    SecretsManager(SecretsManagerSource),
+}
+
+impl ParameterSource for AnySource {
+    async fn fetch_names(
+        &self,
+        names: &[String],
+        with_decryption: bool,
+        exports: &HashMap<String, String>,
+    ) -> Result<NamedFetch> {
+        match self {
+            Self::Ssm(source) => source.fetch_names(names, with_decryption, exports).await,
+            Self::SecretsManager(source) => source.fetch_names(names, with_decryption, exports).await,
+        }
+    }
+
+    async fn fetch_path(&self, path: &str, with_decryption: bool) -> Result<Vec<(String, String)>> {
+        match self {
+            Self::Ssm(source) => source.fetch_path(path, with_decryption).await,
+            Self::SecretsManager(source) => source.fetch_path(path, with_decryption).await,
+        }
+    }
+}
+
+pub(crate) struct SecretsManagerSource {
+    client: SecretsManagerClient,
+    /// Whether a secret whose value is a JSON object should be split into
+    /// one env var per field, per `--secrets-json`. Off by default so a
+    /// secret you mean to consume whole (e.g. a raw JSON config blob) is
+    /// never force-split.
+    parse_json: bool,
+}
+
+impl SecretsManagerSource {
+    pub(crate) fn new(client: SecretsManagerClient, parse_json: bool) -> Self {
+        Self { client, parse_json }
+    }
+}
+
+impl ParameterSource for SecretsManagerSource {
+    async fn fetch_names(
+        &self,
+        names: &[String],
+        _with_decryption: bool,
+        exports: &HashMap<String, String>,
+    ) -> Result<NamedFetch> {
+        let mut params = Vec::new();
+        let mut invalid = Vec::new();
+        for name in names {
+            match self.client.get_secret_value().secret_id(name).send().await {
+                Ok(output) => {
+                    if let Some(value) = output.secret_string {
+                        let alias = exports.get(name).map(String::as_str);
+                        params.extend(expand_secret(name, value, alias, self.parse_json));
+                    }
+                }
+                Err(err)
+                    if err
+                        .as_service_error()
+                        .is_some_and(GetSecretValueError::is_resource_not_found_exception) =>
+                {
+                    invalid.push(name.clone());
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(NamedFetch { params, invalid })
+    }
+
+    async fn fetch_path(&self, path: &str, _with_decryption: bool) -> Result<Vec<(String, String)>> {
+        let prefix = if path.ends_with('/') {
+            path.to_owned()
+        } else {
+            format!("{path}/")
+        };
+
+        let mut params = Vec::new();
+        let mut next_token = None;
+        loop {
+            let output = self
+                .client
+                .list_secrets()
+                .set_next_token(next_token)
+                .send()
+                .await?;
+
+            let names = output
+                .secret_list
+                .into_iter()
+                .flatten()
+                .filter_map(|s| s.name)
+                .filter(|name| {
+                    name.strip_prefix(&prefix)
+                        .is_some_and(|rest| !rest.is_empty() && !rest.contains('/'))
+                });
+
+            for name in names {
+                if let Some(value) = self
+                    .client
+                    .get_secret_value()
+                    .secret_id(&name)
+                    .send()
+                    .await?
+                    .secret_string
+                {
+                    params.extend(expand_secret(&name, value, None, self.parse_json));
+                }
+            }
+
+            next_token = output.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+        Ok(params)
+    }
+}
+
+/// Secrets Manager secrets are free-form strings, but are very often JSON
+/// blobs with several logical values packed into one secret. When
+/// `parse_json` is set and `value` parses as a JSON object, expand it into
+/// one `(field, value)` pair per field — prefixed with `alias` if the
+/// secret has an `--export ALIAS=name` alias, so aliasing still applies to
+/// JSON secrets. Otherwise (including when `parse_json` is unset) keep it
+/// as the single `(name, value)` pair, letting the caller's normal
+/// `--export` rename apply.
+fn expand_secret(name: &str, value: String, alias: Option<&str>, parse_json: bool) -> Vec<(String, String)> {
+    if !parse_json {
+        return vec![(name.to_owned(), value)];
+    }
+
+    match serde_json::from_str::<HashMap<String, serde_json::Value>>(&value) {
+        Ok(fields) => fields
+            .into_iter()
+            .map(|(field, v)| {
+                let value = match v {
+                    serde_json::Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                let key = match alias {
+                    Some(alias) => format!("{alias}_{field}"),
+                    None => field,
+                };
+                (key, value)
+            })
+            .collect(),
+        Err(_) => vec![(name.to_owned(), value)],
+    }
+}
+